@@ -0,0 +1,18 @@
+//! sm-rs-types: Rust types generated from the sm-json-data schemas.
+//!
+//! The schema-merging logic lives in [`merge`], shared with `build.rs` via `#[path]` inclusion so
+//! it's exercised directly by `cargo test` rather than only indirectly through a full build.
+//!
+//! Runtime JSON Schema validation against the generated schema is available behind the `validate`
+//! feature; see [`validate`].
+
+// Most of `merge`'s items are only consumed by `build.rs` (which pulls this same file in via
+// `#[path]`), not from anywhere else in this crate.
+#[allow(dead_code)]
+mod merge;
+
+#[cfg(feature = "validate")]
+mod validate_api;
+
+#[cfg(feature = "validate")]
+pub use validate_api::{ValidationIssue, validate};
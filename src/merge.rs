@@ -0,0 +1,904 @@
+//! The schema-merging logic shared between `build.rs` and this crate.
+//!
+//! `build.rs` can't depend on its own crate (it has to run before the crate it builds), so this
+//! file is pulled into the build script via `#[path]` instead, and into the library normally via
+//! `mod merge;`. Keeping the logic here rather than inline in `build.rs` means it gets exercised
+//! by `cargo test` like everything else, instead of living somewhere the ordinary test harness
+//! never reaches.
+
+use serde_json::{Map, Value as JsonValue, json};
+use std::collections::HashMap;
+use std::env;
+
+/// Uppercase the first character of a string
+pub(crate) fn uppercase_first(s: &str) -> String {
+    let mut iter = s.chars();
+    match iter.next() {
+        None => String::new(),
+        Some(c) => c.to_uppercase().chain(iter).collect(),
+    }
+}
+
+/// Convert the name part of a schema file name to a type name that will be used in the schema.
+/// This turns "foo-bar-baz" into "SchemaFooBarBaz".
+pub(crate) fn schema_name_to_type_name(s: &str) -> String {
+    ["Schema".to_string()]
+        .into_iter()
+        .chain(s.split("-").map(uppercase_first))
+        .collect::<Vec<_>>()
+        .join("")
+}
+
+/// Rewrite JSON $ref instances to work with the merged schema.
+pub(crate) fn rewrite_references(
+    name: &str,
+    value: &mut JsonValue,
+    schema_lookup: &HashMap<String, String>,
+    settings: &MergeSettings,
+) {
+    fn fix_reference(
+        reference: &str,
+        name: &str,
+        schema_lookup: &HashMap<String, String>,
+        settings: &MergeSettings,
+    ) -> String {
+        if reference.starts_with("#/properties/") {
+            // Since we are moving the schemas to definitions, this has to change
+            format!("{}{}/{}", settings.definitions_path, name, &reference[2..])
+        } else if let Some(idx) = reference.find("#")
+            && idx > 0
+        {
+            // This is a reference to one of the other schemas, which will be merged, so strip
+            // the schema name and rewrite the remaining pointer the same way as any other
+            // cross-schema reference.
+            match reference[idx..].strip_prefix("#/definitions/") {
+                Some(rest) => format!("{}{}", settings.definitions_path, rest),
+                None => reference[idx..].to_string(),
+            }
+        } else if !reference.contains("#") {
+            // This is a reference to an entire schema by name. Since it will move to
+            // definitions, refer to its name there instead.
+            if let Some(schema) = schema_lookup.get(reference) {
+                format!("{}{}", settings.definitions_path, schema)
+            } else {
+                panic!("Did not find schema for $ref '{}'", reference);
+            }
+        } else if let Some(rest) = reference.strip_prefix("#/definitions/") {
+            // A reference to one of this schema's own definitions, which are extracted to the
+            // merged definitions as well.
+            format!("{}{}", settings.definitions_path, rest)
+        } else {
+            reference.to_string()
+        }
+    }
+
+    if value.is_object() {
+        value
+            .as_object_mut()
+            .unwrap()
+            .iter_mut()
+            .for_each(|(k, v)| {
+                if k == "$ref" {
+                    *v = fix_reference(v.as_str().unwrap(), name, schema_lookup, settings).into();
+                } else {
+                    rewrite_references(name, v, schema_lookup, settings);
+                }
+            });
+    } else if value.is_array() {
+        value
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .for_each(|v| rewrite_references(name, v, schema_lookup, settings));
+    }
+}
+
+/// Configures where the merged schema's reusable definitions live, and what shape the final
+/// artifact takes.
+///
+/// Cf. schemars' `SchemaSettings`. Selected via the `SM_SCHEMA_OUTPUT` environment variable, since
+/// this is a build-time choice rather than something that needs a CLI surface.
+pub(crate) struct MergeSettings {
+    pub(crate) output_kind: OutputKind,
+    /// Prefix used for every `$ref` pointing into `definitions`, including the trailing slash
+    /// (e.g. `"#/definitions/"`).
+    pub(crate) definitions_path: String,
+}
+
+/// The shape of the merged schema artifact.
+pub(crate) enum OutputKind {
+    /// A plain draft-07 schema with definitions under `#/definitions/`, as expected by Typify.
+    Draft07,
+    /// An OpenAPI 3 `components.schemas` block, so the artifact can be dropped straight into an
+    /// OpenAPI document.
+    OpenApi3,
+}
+
+impl MergeSettings {
+    pub(crate) fn from_env() -> Self {
+        let output_kind = match env::var("SM_SCHEMA_OUTPUT").as_deref() {
+            Ok("openapi3") => OutputKind::OpenApi3,
+            Ok("draft07") | Err(_) => OutputKind::Draft07,
+            Ok(other) => panic!(
+                "Unknown SM_SCHEMA_OUTPUT '{}', expected 'draft07' or 'openapi3'",
+                other
+            ),
+        };
+        let definitions_path = match output_kind {
+            OutputKind::Draft07 => "#/definitions/",
+            OutputKind::OpenApi3 => "#/components/schemas/",
+        }
+        .to_string();
+
+        MergeSettings {
+            output_kind,
+            definitions_path,
+        }
+    }
+}
+
+/// Extract anything found in the "definitions" key of a schema
+pub(crate) fn extract_definitions(definitions: &mut Map<String, JsonValue>, schema: &mut JsonValue) {
+    if let JsonValue::Object(object) = schema
+        && let Some(JsonValue::Object(defs)) = object.remove("definitions")
+    {
+        definitions.extend(defs);
+    }
+}
+
+/// Walks every definition, resolving each `$ref` against `definitions`, and fails the build with
+/// the full list of problems found if any reference is dangling or part of a cycle.
+///
+/// A renamed or missing schema would otherwise surface as a confusing Typify error much later (or
+/// a panic deep in codegen, like the cycle panics dropshot had to fix), rather than at merge time
+/// with the referring definition named.
+pub(crate) fn validate_references(definitions: &Map<String, JsonValue>, settings: &MergeSettings) {
+    let mut errors = Vec::new();
+
+    for (name, schema) in definitions {
+        check_references(
+            schema,
+            definitions,
+            settings,
+            &mut vec![name.clone()],
+            &mut errors,
+        );
+    }
+
+    if !errors.is_empty() {
+        panic!(
+            "Found {} invalid $ref(s) while validating the merged schema:\n{}",
+            errors.len(),
+            errors.join("\n")
+        );
+    }
+}
+
+/// Recursive helper for [`validate_references`].
+///
+/// `stack` tracks the chain of definition names followed to reach `value`, so that a `$ref` cycle
+/// that doesn't pass through a property/array boundary (a pure `$ref` -> `$ref` loop) can be
+/// detected. Such cycles can't be represented as Rust types and would otherwise blow the stack.
+fn check_references(
+    value: &JsonValue,
+    definitions: &Map<String, JsonValue>,
+    settings: &MergeSettings,
+    stack: &mut Vec<String>,
+    errors: &mut Vec<String>,
+) {
+    let Some(map) = value.as_object() else {
+        if let Some(vec) = value.as_array() {
+            for v in vec {
+                check_references(v, definitions, settings, stack, errors);
+            }
+        }
+        return;
+    };
+
+    let Some(JsonValue::String(reference)) = map.get("$ref") else {
+        for v in map.values() {
+            check_references(v, definitions, settings, stack, errors);
+        }
+        return;
+    };
+
+    let Some(pointer) = reference.strip_prefix(settings.definitions_path.as_str()) else {
+        errors.push(format!(
+            "{}: $ref '{}' does not start with expected prefix '{}'",
+            stack.last().unwrap(),
+            reference,
+            settings.definitions_path
+        ));
+        return;
+    };
+
+    let target = pointer.split('/').next().unwrap_or(pointer);
+    let Some(definition) = definitions.get(target) else {
+        errors.push(format!(
+            "{}: $ref '{}' does not resolve to any known definition",
+            stack.last().unwrap(),
+            reference
+        ));
+        return;
+    };
+
+    // `target` is only the leading path segment (the definition name); walk the rest of the
+    // pointer (e.g. `/properties/someProp`) into that definition to make sure the whole thing
+    // resolves, not just the name at the front.
+    let tail = &pointer[target.len()..];
+    let resolved = if tail.is_empty() {
+        definition
+    } else {
+        let Some(resolved) = definition.pointer(tail) else {
+            errors.push(format!(
+                "{}: $ref '{}' resolves to definition '{}', but pointer '{}' does not exist \
+                 there",
+                stack.last().unwrap(),
+                reference,
+                target,
+                tail
+            ));
+            return;
+        };
+        resolved
+    };
+
+    // Only a pure $ref (nothing else in the object) continues the chain for cycle-detection
+    // purposes; a $ref tucked inside properties/items passes through a boundary Typify can
+    // represent with a Box, so it isn't a problematic cycle.
+    if resolved
+        .as_object()
+        .is_some_and(|o| o.len() == 1 && o.contains_key("$ref"))
+    {
+        if stack.contains(&target.to_string()) {
+            errors.push(format!(
+                "Cycle detected: {} -> {}",
+                stack.join(" -> "),
+                target
+            ));
+        } else {
+            stack.push(target.to_string());
+            check_references(resolved, definitions, settings, stack, errors);
+            stack.pop();
+        }
+    }
+}
+
+/// A rewrite applied to every merged schema.
+///
+/// Modeled on schemars' `Transform`. Each Typify incompatibility sm-json-data grows gets its own
+/// `Transform` impl, registered in the pipeline built in `main`, instead of another ad-hoc
+/// function spliced into the merge loop.
+pub(crate) trait Transform {
+    fn transform(&mut self, schema: &mut JsonValue);
+
+    /// Like [`Transform::transform`], but told which top-level definition it's operating on.
+    ///
+    /// Transforms that need to log which construct they rewrote where (so maintainers can audit
+    /// data-model drift when the sm-json-data submodule updates) override this instead; everything
+    /// else gets it for free in terms of `transform`. Implementations typically do so by stashing
+    /// `name` in a `current_name: Option<String>` field, set here and read by the `Transform`
+    /// recursive calls the override kicks off, so nested calls can still attribute their warnings
+    /// correctly.
+    fn transform_named(&mut self, name: &str, schema: &mut JsonValue) {
+        let _ = name;
+        self.transform(schema);
+    }
+}
+
+/// Recurses into every schema-composition keyword that can carry a nested schema
+/// (`properties`/`patternProperties`/`dependentSchemas` values, `items`, `additionalProperties`,
+/// `allOf`/`anyOf`/`oneOf`, `if`/`then`/`else`, `not`, `definitions`), applying `transform` to
+/// each subschema found.
+///
+/// Every [`Transform`] impl in this file uses this for its recursive step, so a new Typify
+/// incompatibility only needs its own local rewrite, not another hand-rolled walk of the schema
+/// tree.
+pub(crate) fn transform_subschemas<T: Transform + ?Sized>(transform: &mut T, schema: &mut JsonValue) {
+    let Some(object) = schema.as_object_mut() else {
+        return;
+    };
+
+    for keyword in [
+        "properties",
+        "patternProperties",
+        "dependentSchemas",
+        "definitions",
+    ] {
+        if let Some(map) = object.get_mut(keyword).and_then(JsonValue::as_object_mut) {
+            for value in map.values_mut() {
+                transform.transform(value);
+            }
+        }
+    }
+
+    for keyword in ["items", "additionalProperties", "if", "then", "else", "not"] {
+        if let Some(value) = object.get_mut(keyword) {
+            transform.transform(value);
+        }
+    }
+
+    for keyword in ["allOf", "anyOf", "oneOf"] {
+        if let Some(subschemas) = object.get_mut(keyword).and_then(JsonValue::as_array_mut) {
+            for subschema in subschemas {
+                transform.transform(subschema);
+            }
+        }
+    }
+}
+
+/// Convenience wrapper for reading and writing keys on a schema that might be a bare `true`/
+/// `false` boolean schema rather than an object.
+///
+/// JSON Schema allows `true` ("anything goes") and `false` ("nothing allowed") as schemas in
+/// their own right. `insert` treats `true` as the equivalent empty object schema before inserting,
+/// so callers don't need a special case for it; `false` has no object equivalent and is left
+/// alone.
+struct SchemaExt<'a>(&'a mut JsonValue);
+
+impl SchemaExt<'_> {
+    fn insert(&mut self, key: &str, value: JsonValue) {
+        if matches!(self.0, JsonValue::Bool(true)) {
+            *self.0 = json!({});
+        }
+        if let Some(object) = self.0.as_object_mut() {
+            object.insert(key.to_string(), value);
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&JsonValue> {
+        self.0.as_object().and_then(|o| o.get(key))
+    }
+}
+
+/// Strips if/then/else constructs, which Typify does not support, while preserving the properties
+/// they introduce.
+///
+/// sm-json-data uses if/then/else to make some properties required only under a condition. Typify
+/// has no equivalent, so rather than discard the whole construct (and the properties it only
+/// mentions in a branch) we union every property reachable through any branch into the enclosing
+/// object's own `properties`, without touching `required`. The net effect is that those properties
+/// become optional fields on the generated type (Typify emits `Option<T>`), so no part of the data
+/// model is lost.
+pub(crate) struct StripIfThenElse;
+
+impl Transform for StripIfThenElse {
+    fn transform(&mut self, schema: &mut JsonValue) {
+        if let Some(map) = schema.as_object_mut()
+            && map.contains_key("if")
+            && map.contains_key("then")
+        {
+            flatten_if_then_else(map);
+        }
+        transform_subschemas(self, schema);
+    }
+}
+
+/// Folds a single if/then/else construct on `map` into `map`'s own `properties`, then removes the
+/// `if`/`then`/`else` keys.
+fn flatten_if_then_else(map: &mut Map<String, JsonValue>) {
+    let mut collected = Map::new();
+    for key in ["if", "then", "else"] {
+        if let Some(mut branch) = map.remove(key) {
+            collect_branch_properties(&mut branch, &mut collected);
+        }
+    }
+
+    if collected.is_empty() {
+        return;
+    }
+
+    let properties = map.entry("properties").or_insert_with(|| json!({}));
+    let mut properties = SchemaExt(properties);
+    for (key, value) in collected {
+        if properties.get(&key).is_none() {
+            properties.insert(&key, value);
+        }
+    }
+}
+
+/// Collects the properties declared by a single if/then/else branch into `into`, without
+/// overwriting keys already present.
+///
+/// A branch that's a bare `$ref` is skipped, since its properties live in the referenced
+/// definition rather than here. A branch carrying its own nested if/then/else is flattened first,
+/// so properties from arbitrarily deeply nested conditionals are all captured.
+fn collect_branch_properties(branch: &mut JsonValue, into: &mut Map<String, JsonValue>) {
+    if let JsonValue::Object(map) = branch
+        && map.contains_key("if")
+        && map.contains_key("then")
+    {
+        flatten_if_then_else(map);
+    }
+
+    let branch = SchemaExt(branch);
+    if branch.get("$ref").is_some() {
+        return;
+    }
+    if let Some(JsonValue::Object(properties)) = branch.get("properties") {
+        for (key, value) in properties.clone() {
+            into.entry(key).or_insert(value);
+        }
+    }
+}
+
+/// Translates `patternProperties` into a permissive `additionalProperties`, since Typify does not
+/// support pattern-keyed properties.
+///
+/// The replacement is the union (as `anyOf`) of the pattern value schemas, provided they all agree
+/// on a `type`; if they don't, there's no single sensible schema to fall back to, so this falls
+/// back to a bare `true` (anything goes) instead.
+#[derive(Default)]
+pub(crate) struct PatternPropertiesToAdditionalProperties {
+    /// See [`Transform::transform_named`].
+    current_name: Option<String>,
+}
+
+impl PatternPropertiesToAdditionalProperties {
+    fn rewrite(&self, schema: &mut JsonValue) {
+        let Some(map) = schema.as_object_mut() else {
+            return;
+        };
+
+        let Some(JsonValue::Object(patterns)) = map.remove("patternProperties") else {
+            return;
+        };
+
+        if map.contains_key("additionalProperties") {
+            // An explicit additionalProperties (often `false`, to forbid any other keys) already
+            // constrains this object. Replacing it with a patternProperties-derived schema would
+            // loosen or silently change that constraint, so it's left untouched; patternProperties
+            // is simply dropped, since Typify can't represent it either way.
+            if let Some(name) = &self.current_name {
+                println!(
+                    "cargo::warning=Dropped patternProperties in '{}' (additionalProperties already present)",
+                    name
+                );
+            }
+            return;
+        }
+
+        let values: Vec<JsonValue> = patterns.into_values().collect();
+        let types: Vec<_> = values.iter().filter_map(|v| v.get("type")).collect();
+        let replacement = match values.len() {
+            0 => json!(true),
+            1 => values.into_iter().next().unwrap(),
+            _ if types.len() == values.len() && types.windows(2).all(|w| w[0] == w[1]) => {
+                json!({ "anyOf": values })
+            }
+            _ => json!(true),
+        };
+        if let Some(name) = &self.current_name {
+            println!(
+                "cargo::warning=Rewrote patternProperties to additionalProperties in '{}'",
+                name
+            );
+        }
+        map.insert("additionalProperties".to_string(), replacement);
+    }
+}
+
+impl Transform for PatternPropertiesToAdditionalProperties {
+    fn transform(&mut self, schema: &mut JsonValue) {
+        self.rewrite(schema);
+        transform_subschemas(self, schema);
+    }
+
+    fn transform_named(&mut self, name: &str, schema: &mut JsonValue) {
+        self.current_name = Some(name.to_string());
+        self.transform(schema);
+    }
+}
+
+/// Drops `dependentSchemas`/`dependentRequired`, which Typify does not support, after hoisting any
+/// properties `dependentSchemas` introduces into the parent's own `properties` as optional.
+///
+/// `dependentRequired` only lists already-declared sibling properties as conditionally required,
+/// so there's nothing to hoist for it -- it's simply dropped.
+#[derive(Default)]
+pub(crate) struct DependentSchemas {
+    /// See [`Transform::transform_named`].
+    current_name: Option<String>,
+}
+
+impl DependentSchemas {
+    fn rewrite(&self, schema: &mut JsonValue) {
+        let Some(map) = schema.as_object_mut() else {
+            return;
+        };
+
+        let mut rewrote = false;
+
+        if let Some(JsonValue::Object(dependent)) = map.remove("dependentSchemas") {
+            rewrote = true;
+            let mut collected = Map::new();
+            for mut dependency in dependent.into_values() {
+                if let Some(JsonValue::Object(properties)) = dependency
+                    .as_object_mut()
+                    .and_then(|o| o.remove("properties"))
+                {
+                    for (key, value) in properties {
+                        collected.entry(key).or_insert(value);
+                    }
+                }
+            }
+            if !collected.is_empty() {
+                let JsonValue::Object(properties) =
+                    map.entry("properties").or_insert_with(|| json!({}))
+                else {
+                    unreachable!("properties is always an object here");
+                };
+                for (key, value) in collected {
+                    properties.entry(key).or_insert(value);
+                }
+            }
+        }
+
+        rewrote |= map.remove("dependentRequired").is_some();
+
+        if rewrote && let Some(name) = &self.current_name {
+            println!(
+                "cargo::warning=Rewrote dependentSchemas/dependentRequired in '{}'",
+                name
+            );
+        }
+    }
+}
+
+impl Transform for DependentSchemas {
+    fn transform(&mut self, schema: &mut JsonValue) {
+        self.rewrite(schema);
+        transform_subschemas(self, schema);
+    }
+
+    fn transform_named(&mut self, name: &str, schema: &mut JsonValue) {
+        self.current_name = Some(name.to_string());
+        self.transform(schema);
+    }
+}
+
+/// Strips `propertyNames`, which Typify does not support and which sm-json-data only uses to
+/// constrain map keys -- a constraint with no equivalent on a plain Rust struct field.
+#[derive(Default)]
+pub(crate) struct PropertyNames {
+    /// See [`Transform::transform_named`].
+    current_name: Option<String>,
+}
+
+impl PropertyNames {
+    fn rewrite(&self, schema: &mut JsonValue) {
+        let Some(map) = schema.as_object_mut() else {
+            return;
+        };
+
+        if map.remove("propertyNames").is_some()
+            && let Some(name) = &self.current_name
+        {
+            println!("cargo::warning=Stripped propertyNames in '{}'", name);
+        }
+    }
+}
+
+impl Transform for PropertyNames {
+    fn transform(&mut self, schema: &mut JsonValue) {
+        self.rewrite(schema);
+        transform_subschemas(self, schema);
+    }
+
+    fn transform_named(&mut self, name: &str, schema: &mut JsonValue) {
+        self.current_name = Some(name.to_string());
+        self.transform(schema);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_subschemas_recurses_through_every_composition_keyword() {
+        struct CountBools(usize);
+        impl Transform for CountBools {
+            fn transform(&mut self, schema: &mut JsonValue) {
+                if matches!(schema, JsonValue::Bool(_)) {
+                    self.0 += 1;
+                }
+                transform_subschemas(self, schema);
+            }
+        }
+
+        let mut schema = json!({
+            "properties": { "a": true },
+            "patternProperties": { "^x-": true },
+            "dependentSchemas": { "a": true },
+            "definitions": { "Foo": true },
+            "items": true,
+            "additionalProperties": true,
+            "if": true,
+            "then": true,
+            "else": true,
+            "not": true,
+            "allOf": [true],
+            "anyOf": [true],
+            "oneOf": [true],
+        });
+
+        let mut counter = CountBools(0);
+        counter.transform(&mut schema);
+
+        assert_eq!(counter.0, 13);
+    }
+
+    #[test]
+    fn schema_ext_insert_upgrades_bare_true_schema_to_an_object() {
+        let mut schema = json!(true);
+        let mut ext = SchemaExt(&mut schema);
+        ext.insert("type", json!("string"));
+        assert_eq!(schema, json!({ "type": "string" }));
+    }
+
+    #[test]
+    fn schema_ext_insert_leaves_bare_false_schema_alone() {
+        let mut schema = json!(false);
+        let mut ext = SchemaExt(&mut schema);
+        ext.insert("type", json!("string"));
+        assert_eq!(schema, json!(false));
+    }
+
+    #[test]
+    fn strip_if_then_else_unions_properties_from_every_branch() {
+        let mut schema = json!({
+            "type": "object",
+            "properties": { "kind": { "type": "string" } },
+            "if": { "properties": { "kind": { "const": "a" } } },
+            "then": { "properties": { "aOnly": { "type": "string" } } },
+            "else": { "properties": { "bOnly": { "type": "string" } } },
+        });
+
+        StripIfThenElse.transform(&mut schema);
+
+        assert!(schema.get("if").is_none());
+        assert!(schema.get("then").is_none());
+        assert!(schema.get("else").is_none());
+        assert_eq!(
+            schema["properties"]["aOnly"],
+            json!({ "type": "string" })
+        );
+        assert_eq!(
+            schema["properties"]["bOnly"],
+            json!({ "type": "string" })
+        );
+    }
+
+    #[test]
+    fn strip_if_then_else_flattens_nested_conditionals() {
+        let mut schema = json!({
+            "type": "object",
+            "if": { "properties": { "kind": { "const": "a" } } },
+            "then": {
+                "if": { "properties": { "sub": { "const": "x" } } },
+                "then": { "properties": { "deep": { "type": "string" } } },
+            },
+        });
+
+        StripIfThenElse.transform(&mut schema);
+
+        assert!(schema.get("if").is_none());
+        assert_eq!(
+            schema["properties"]["deep"],
+            json!({ "type": "string" })
+        );
+    }
+
+    #[test]
+    fn strip_if_then_else_skips_a_bare_ref_branch() {
+        let mut schema = json!({
+            "type": "object",
+            "if": { "const": "a" },
+            "then": { "$ref": "#/definitions/SchemaFoo" },
+        });
+
+        StripIfThenElse.transform(&mut schema);
+
+        assert!(schema.get("if").is_none());
+        // The $ref branch's properties live in the referenced definition, not here, so nothing
+        // should have been hoisted for it.
+        assert_eq!(schema.get("properties"), None);
+    }
+
+    #[test]
+    fn strip_if_then_else_does_not_overwrite_an_existing_property() {
+        let mut schema = json!({
+            "type": "object",
+            "properties": { "kind": { "type": "integer" } },
+            "if": { "properties": { "other": { "const": "a" } } },
+            "then": { "properties": { "kind": { "type": "string" } } },
+        });
+
+        StripIfThenElse.transform(&mut schema);
+
+        // The enclosing object's own declaration wins over whatever the branch says.
+        assert_eq!(schema["properties"]["kind"], json!({ "type": "integer" }));
+    }
+
+    fn draft07_settings() -> MergeSettings {
+        MergeSettings {
+            output_kind: OutputKind::Draft07,
+            definitions_path: "#/definitions/".to_string(),
+        }
+    }
+
+    #[test]
+    fn validate_references_accepts_a_resolvable_ref() {
+        let definitions: Map<_, _> = serde_json::from_value(json!({
+            "SchemaFoo": {
+                "type": "object",
+                "properties": { "bar": { "$ref": "#/definitions/SchemaBar" } },
+            },
+            "SchemaBar": { "type": "string" },
+        }))
+        .unwrap();
+
+        validate_references(&definitions, &draft07_settings());
+    }
+
+    #[test]
+    #[should_panic(expected = "does not resolve to any known definition")]
+    fn validate_references_rejects_a_dangling_ref() {
+        let definitions: Map<_, _> = serde_json::from_value(json!({
+            "SchemaFoo": { "$ref": "#/definitions/SchemaMissing" },
+        }))
+        .unwrap();
+
+        validate_references(&definitions, &draft07_settings());
+    }
+
+    #[test]
+    #[should_panic(expected = "does not exist there")]
+    fn validate_references_rejects_a_dangling_pointer_tail() {
+        let definitions: Map<_, _> = serde_json::from_value(json!({
+            "SchemaFoo": { "$ref": "#/definitions/SchemaBar/properties/typo" },
+            "SchemaBar": {
+                "type": "object",
+                "properties": { "real": { "type": "string" } },
+            },
+        }))
+        .unwrap();
+
+        validate_references(&definitions, &draft07_settings());
+    }
+
+    #[test]
+    #[should_panic(expected = "Cycle detected")]
+    fn validate_references_rejects_a_pure_ref_cycle() {
+        let definitions: Map<_, _> = serde_json::from_value(json!({
+            "SchemaFoo": { "$ref": "#/definitions/SchemaBar" },
+            "SchemaBar": { "$ref": "#/definitions/SchemaFoo" },
+        }))
+        .unwrap();
+
+        validate_references(&definitions, &draft07_settings());
+    }
+
+    #[test]
+    fn validate_references_allows_a_ref_cycle_through_a_property_boundary() {
+        // A $ref tucked inside `properties` (rather than a pure `$ref`-only schema) passes
+        // through a boundary Typify can represent with a Box, so it isn't rejected as a cycle.
+        let definitions: Map<_, _> = serde_json::from_value(json!({
+            "SchemaFoo": {
+                "type": "object",
+                "properties": { "bar": { "$ref": "#/definitions/SchemaBar" } },
+            },
+            "SchemaBar": {
+                "type": "object",
+                "properties": { "foo": { "$ref": "#/definitions/SchemaFoo" } },
+            },
+        }))
+        .unwrap();
+
+        validate_references(&definitions, &draft07_settings());
+    }
+
+    #[test]
+    fn pattern_properties_becomes_additional_properties_when_theres_a_single_pattern() {
+        let mut schema = json!({
+            "type": "object",
+            "patternProperties": { "^x-": { "type": "string" } },
+        });
+
+        PatternPropertiesToAdditionalProperties::default().rewrite(&mut schema);
+
+        assert!(schema.get("patternProperties").is_none());
+        assert_eq!(schema["additionalProperties"], json!({ "type": "string" }));
+    }
+
+    #[test]
+    fn pattern_properties_unions_agreeing_types_into_any_of() {
+        let mut schema = json!({
+            "type": "object",
+            "patternProperties": {
+                "^x-": { "type": "string" },
+                "^y-": { "type": "string", "minLength": 1 },
+            },
+        });
+
+        PatternPropertiesToAdditionalProperties::default().rewrite(&mut schema);
+
+        let additional = &schema["additionalProperties"];
+        let any_of = additional["anyOf"].as_array().unwrap();
+        assert_eq!(any_of.len(), 2);
+    }
+
+    #[test]
+    fn pattern_properties_falls_back_to_true_on_disagreeing_types() {
+        let mut schema = json!({
+            "type": "object",
+            "patternProperties": {
+                "^x-": { "type": "string" },
+                "^y-": { "type": "integer" },
+            },
+        });
+
+        PatternPropertiesToAdditionalProperties::default().rewrite(&mut schema);
+
+        assert_eq!(schema["additionalProperties"], json!(true));
+    }
+
+    #[test]
+    fn pattern_properties_preserves_an_existing_additional_properties() {
+        let mut schema = json!({
+            "type": "object",
+            "additionalProperties": false,
+            "patternProperties": { "^x-": { "type": "string" } },
+        });
+
+        PatternPropertiesToAdditionalProperties::default().rewrite(&mut schema);
+
+        assert!(schema.get("patternProperties").is_none());
+        assert_eq!(schema["additionalProperties"], json!(false));
+    }
+
+    #[test]
+    fn dependent_schemas_hoists_properties_as_optional() {
+        let mut schema = json!({
+            "type": "object",
+            "properties": { "creditCard": { "type": "string" } },
+            "dependentSchemas": {
+                "creditCard": {
+                    "properties": { "billingAddress": { "type": "string" } },
+                },
+            },
+        });
+
+        DependentSchemas::default().rewrite(&mut schema);
+
+        assert!(schema.get("dependentSchemas").is_none());
+        assert_eq!(
+            schema["properties"]["billingAddress"],
+            json!({ "type": "string" })
+        );
+        // Hoisted properties don't become required -- only optional on the Rust side.
+        assert!(schema.get("required").is_none());
+    }
+
+    #[test]
+    fn dependent_schemas_drops_dependent_required_without_hoisting_anything() {
+        let mut schema = json!({
+            "type": "object",
+            "properties": {
+                "creditCard": { "type": "string" },
+                "billingAddress": { "type": "string" },
+            },
+            "dependentRequired": { "creditCard": ["billingAddress"] },
+        });
+
+        DependentSchemas::default().rewrite(&mut schema);
+
+        assert!(schema.get("dependentRequired").is_none());
+        // Already-declared sibling properties are untouched.
+        assert_eq!(
+            schema["properties"]["billingAddress"],
+            json!({ "type": "string" })
+        );
+    }
+}
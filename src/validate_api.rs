@@ -0,0 +1,194 @@
+//! Runtime validation of sm-json-data documents against the schema generated by `build.rs`.
+//!
+//! Gated behind the `validate` feature: most consumers only want the Typify-generated types, not
+//! a JSON Schema validator pulled in as a dependency.
+
+use jsonschema::{JSONSchema, SchemaResolver, SchemaResolverError};
+use serde_json::{Value as JsonValue, json};
+use std::sync::Arc;
+use url::Url;
+
+/// The schema merged by `build.rs` from the sm-json-data submodule.
+///
+/// The merge process rewrites every cross-schema `$ref` into an internal pointer, so this is
+/// fully self-contained and needs no remote resolution for ordinary use.
+static TOTAL_SCHEMA: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/generated/m3-total.schema.json"));
+
+/// The local JSON pointer prefix under which `build.rs` placed every definition (e.g.
+/// `"/definitions/"` or `"/components/schemas/"`), baked in by `build.rs` itself so this stays in
+/// sync with whatever `SM_SCHEMA_OUTPUT` chose, rather than guessing at the shape here.
+static DEFINITIONS_PREFIX: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/generated/schema_pointer_prefix.txt"
+));
+
+/// Adapts a trait-object resolver to the concrete, sized type `JSONSchema::options().with_resolver`
+/// requires.
+struct ResolverHandle(Arc<dyn SchemaResolver>);
+
+impl SchemaResolver for ResolverHandle {
+    fn resolve(
+        &self,
+        root_schema: &JsonValue,
+        url: &Url,
+        original_reference: &str,
+    ) -> Result<Arc<JsonValue>, SchemaResolverError> {
+        self.0.resolve(root_schema, url, original_reference)
+    }
+}
+
+/// A single validation failure, with the JSON pointer into the instance that caused it.
+#[derive(Debug, Clone)]
+pub struct ValidationIssue {
+    /// JSON pointer (e.g. `/rooms/0/nodes/3`) to the offending part of the instance.
+    pub instance_path: String,
+    pub message: String,
+}
+
+/// Validates `instance` against the definition named `definition` (e.g. `"SchemaRegion"`) in the
+/// generated total schema.
+///
+/// `resolver` is a hook for resolving references outside the total schema (cf. jsonschema-rs'
+/// `SchemaResolver`); pass `None` for the common case, since the merge process already makes the
+/// total schema self-contained.
+pub fn validate(
+    definition: &str,
+    instance: &JsonValue,
+    resolver: Option<Arc<dyn SchemaResolver>>,
+) -> Result<(), Vec<ValidationIssue>> {
+    let total_schema: JsonValue =
+        serde_json::from_str(TOTAL_SCHEMA).expect("generated total schema is not valid JSON");
+
+    validate_against(
+        &total_schema,
+        DEFINITIONS_PREFIX,
+        definition,
+        instance,
+        resolver,
+    )
+}
+
+/// Does the actual work of [`validate`], parameterized over the merged document and its
+/// definitions prefix so both can be supplied as hand-built fixtures in tests, without needing a
+/// real `build.rs` run.
+fn validate_against(
+    total_schema: &JsonValue,
+    definitions_prefix: &str,
+    definition: &str,
+    instance: &JsonValue,
+    resolver: Option<Arc<dyn SchemaResolver>>,
+) -> Result<(), Vec<ValidationIssue>> {
+    let pointer = format!("{}{}", definitions_prefix, definition);
+    if total_schema.pointer(&pointer).is_none() {
+        panic!("No definition named '{}' in the total schema", definition);
+    }
+
+    // Compile against the whole merged document, with an added `$ref` pointing at the requested
+    // definition, rather than just the extracted subtree: a definition that itself contains
+    // `$ref`s to its siblings (the common case) can only resolve them while they're still
+    // reachable from the document root.
+    let mut root = total_schema.clone();
+    root.as_object_mut()
+        .expect("generated total schema is not an object")
+        .insert("$ref".to_string(), json!(format!("#{}", pointer)));
+
+    let mut options = JSONSchema::options();
+    if let Some(resolver) = resolver {
+        options.with_resolver(ResolverHandle(resolver));
+    }
+    let compiled = options
+        .compile(&root)
+        .expect("generated total schema definition failed to compile");
+
+    compiled.validate(instance).map_err(|errors| {
+        errors
+            .map(|error| ValidationIssue {
+                instance_path: error.instance_path.to_string(),
+                message: error.to_string(),
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn resolves_sibling_refs_in_a_draft07_shaped_document() {
+        let total_schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "definitions": {
+                "SchemaFoo": {
+                    "type": "object",
+                    "properties": { "bar": { "$ref": "#/definitions/SchemaBar" } },
+                    "required": ["bar"],
+                },
+                "SchemaBar": { "type": "string" },
+            },
+        });
+
+        assert!(
+            validate_against(
+                &total_schema,
+                "/definitions/",
+                "SchemaFoo",
+                &json!({ "bar": "hello" }),
+                None,
+            )
+            .is_ok()
+        );
+        assert!(
+            validate_against(
+                &total_schema,
+                "/definitions/",
+                "SchemaFoo",
+                &json!({ "bar": 42 }),
+                None,
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn resolves_sibling_refs_in_an_openapi3_shaped_document() {
+        let total_schema = json!({
+            "components": {
+                "schemas": {
+                    "SchemaFoo": {
+                        "type": "object",
+                        "properties": { "bar": { "$ref": "#/components/schemas/SchemaBar" } },
+                        "required": ["bar"],
+                    },
+                    "SchemaBar": { "type": "string" },
+                },
+            },
+        });
+
+        assert!(
+            validate_against(
+                &total_schema,
+                "/components/schemas/",
+                "SchemaFoo",
+                &json!({ "bar": "hello" }),
+                None,
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "No definition named 'SchemaMissing'")]
+    fn panics_on_unknown_definition() {
+        let total_schema = json!({ "definitions": {} });
+        let _ = validate_against(
+            &total_schema,
+            "/definitions/",
+            "SchemaMissing",
+            &json!({}),
+            None,
+        );
+    }
+}